@@ -3,8 +3,14 @@
 //! Genomics Extension for SQLite
 //!
 //! Installation & programming guide: [https://mlin.github.io/GenomicSQLite/](https://mlin.github.io/GenomicSQLite/)
-use json::object::Object;
-use rusqlite::{params, Connection, LoadExtensionGuard, OpenFlags, Result, NO_PARAMS};
+use rusqlite::backup::Backup;
+use rusqlite::blob::Blob;
+use rusqlite::functions::FunctionFlags;
+#[cfg(feature = "session")]
+use rusqlite::session::{ConflictAction, ConflictType, Session};
+use rusqlite::{params, Connection, DatabaseName, LoadExtensionGuard, OpenFlags, Result, NO_PARAMS};
+use serde::Serialize;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::env;
 #[cfg(feature = "bundle_libgenomicsqlite")]
@@ -13,6 +19,7 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 use std::sync::Once;
+use std::time::Duration;
 use tempfile::TempDir;
 
 /* Helper functions for bundling libgenomicsqlite.{so,dylib} into the compilation unit */
@@ -87,8 +94,22 @@ where
     ans
 }
 
+// translate a schema name, with "" meaning the main database, to rusqlite's DatabaseName
+fn database_name(schema: &str) -> DatabaseName {
+    if schema.is_empty() {
+        DatabaseName::Main
+    } else {
+        DatabaseName::Attached(schema)
+    }
+}
+
 static START: Once = Once::new();
 
+/// GenomicSQLite tuning/config object, for use with [open]. Historically keyed & valued as raw
+/// JSON (an alias for [serde_json::Map]); new code should prefer [open_with] with a typed config
+/// such as [TuningOptions].
+pub type Object = Map<String, Value>;
+
 /// Open a [rusqlite::Connection] for a compressed database with the [ConnectionMethods] available.
 ///
 /// # Arguments
@@ -105,11 +126,21 @@ static START: Once = Once::new();
 /// let conn = genomicsqlite::open(
 ///     "/tmp/rustdoc_example.genomicsqlite",
 ///     rusqlite::OpenFlags::SQLITE_OPEN_CREATE | rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
-///     &json::object::Object::new()
+///     &genomicsqlite::Object::new()
 /// );
 /// println!("GenomicSQLite {}", conn.unwrap().genomicsqlite_version());
 /// ```
 pub fn open<P: AsRef<Path>>(path: P, flags: OpenFlags, config: &Object) -> Result<Connection> {
+    open_with(path, flags, config)
+}
+
+/// Like [open], but accepts any `config` implementing [serde::Serialize] -- e.g. [TuningOptions]
+/// -- instead of the stringly-typed [Object], so tuning options are checked at compile time.
+pub fn open_with<P: AsRef<Path>, C: Serialize>(
+    path: P,
+    flags: OpenFlags,
+    config: &C,
+) -> Result<Connection> {
     // once: load libgenomicsqlite extension
     START.call_once(|| {
         let mut _tmpdir;
@@ -136,7 +167,8 @@ pub fn open<P: AsRef<Path>>(path: P, flags: OpenFlags, config: &Object) -> Resul
 
     // generate config & connection strings
     let memconn = Connection::open_in_memory().unwrap();
-    let config_json_str = config.dump();
+    let config_json_str = serde_json::to_string(config)
+        .map_err(|e| rusqlite::Error::ModuleError(format!("genomicsqlite::open_with: {}", e)))?;
     let uri: String = query1str(
         &memconn,
         "SELECT genomicsqlite_uri(?,?)",
@@ -157,6 +189,48 @@ pub fn open<P: AsRef<Path>>(path: P, flags: OpenFlags, config: &Object) -> Resul
     Ok(conn)
 }
 
+/// Strongly-typed GenomicSQLite tuning options, for use with [open_with] instead of the
+/// stringly-typed [Object]-based [open]. See the
+/// [Programming Guide](https://mlin.github.io/GenomicSQLite/guide/#tuning-options) for each
+/// field's semantics. Fields left `None` are omitted from the generated configuration JSON, so
+/// GenomicSQLite's own defaults apply.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TuningOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<i64>,
+    #[serde(rename = "inner_page_KiB", skip_serializing_if = "Option::is_none")]
+    pub inner_page_kib: Option<i64>,
+    #[serde(rename = "outer_page_KiB", skip_serializing_if = "Option::is_none")]
+    pub outer_page_kib: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zstd_level: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsafe_load: Option<bool>,
+}
+
+/// Like [open_with], but also installs busy-handling (via [rusqlite::Connection::busy_timeout]
+/// and/or [rusqlite::Connection::busy_handler]) so a reader opened alongside a writer waits
+/// instead of immediately failing with `SQLITE_BUSY`. `busy_handler` is invoked with the retry
+/// count so far, as a bare `fn` (SQLite's callback is not given a closure environment). Note the
+/// compressing VFS doesn't support WAL, so this only arbitrates its own page-level lock, not
+/// true multi-writer concurrency.
+pub fn open_with_busy<P: AsRef<Path>, C: Serialize>(
+    path: P,
+    flags: OpenFlags,
+    config: &C,
+    busy_timeout_ms: Option<u64>,
+    busy_handler: Option<fn(i32) -> bool>,
+) -> Result<Connection> {
+    let conn = open_with(path, flags, config)?;
+    if let Some(ms) = busy_timeout_ms {
+        conn.busy_timeout(Duration::from_millis(ms))?;
+    }
+    if let Some(handler) = busy_handler {
+        conn.busy_handler(Some(handler))?;
+    }
+    Ok(conn)
+}
+
 /// Genomic reference sequence metadata
 #[derive(Clone)]
 pub struct RefSeq {
@@ -165,7 +239,7 @@ pub struct RefSeq {
     pub length: i64,
     pub assembly: Option<String>,
     pub refget_id: Option<String>,
-    pub meta_json: Object,
+    pub meta_json: Value,
 }
 
 /// Methods for GenomicSQLite [rusqlite::Connection]s; see [Programming Guide](https://mlin.github.io/GenomicSQLite/guide/)
@@ -239,6 +313,172 @@ pub trait ConnectionMethods {
         assembly: Option<&str>,
         schema: Option<&str>,
     ) -> Result<HashMap<String, RefSeq>>;
+
+    /// Copy this (possibly live) database into a freshly-created GenomicSQLite database at
+    /// `dest_path`, via SQLite's online backup API rather than one blocking `VACUUM INTO`
+    /// statement. Leave `pages_per_step` large to avoid thrashing the destination's zstd cache.
+    fn backup_genomicsqlite<P: AsRef<Path>>(
+        &self,
+        dest_path: P,
+        dest_config: &Object,
+        pages_per_step: i32,
+        pause_between_pages: Duration,
+        progress: Option<&mut dyn FnMut(rusqlite::backup::Progress)>,
+    ) -> Result<()>;
+
+    /// Open an incremental I/O stream ([rusqlite::blob::Blob]) onto one BLOB or TEXT value,
+    /// without reading or writing it in one piece. Pass `schema: ""` for the main database.
+    fn open_genomic_blob(
+        &self,
+        schema: &str,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        read_only: bool,
+    ) -> Result<Blob>;
+
+    /// Like [ConnectionMethods::open_genomic_blob], but streams a `column` out of every row
+    /// matching a `genomic_range_rowids(table,rid,beg,end)` query, reusing one `Blob` handle.
+    fn open_genomic_blob_range(
+        &self,
+        schema: &str,
+        table: &str,
+        column: &str,
+        rid: i64,
+        beg: i64,
+        end: i64,
+        read_only: bool,
+    ) -> Result<GenomicBlobRange>;
+
+    /// Register the `gri_overlap`, `gri_contains`, `gri_merge`, and `gri_merge_length` SQL
+    /// functions on this connection, for interval arithmetic that complements the GRI.
+    fn create_genomic_interval_functions(&self) -> Result<()>;
+}
+
+/// Streams a `column` BLOB/TEXT value out of each row matching a genomic range query, reusing
+/// one [rusqlite::blob::Blob] handle across rows; see
+/// [ConnectionMethods::open_genomic_blob_range].
+pub struct GenomicBlobRange<'conn> {
+    conn: &'conn Connection,
+    schema: String,
+    table: String,
+    column: String,
+    read_only: bool,
+    rowids: std::vec::IntoIter<i64>,
+    blob: Option<Blob<'conn>>,
+}
+
+impl<'conn> GenomicBlobRange<'conn> {
+    /// Advance to the next matching row, re-pointing the underlying `Blob` at it, and return a
+    /// mutable reference to it for reading/writing/seeking. Returns `None` once every matching
+    /// row has been visited.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<&mut Blob<'conn>>> {
+        let rowid = self.rowids.next()?;
+        let opened = match self.blob.take() {
+            Some(mut blob) => blob.reopen(rowid).map(|()| blob),
+            None => self.conn.blob_open(
+                database_name(&self.schema),
+                &self.table,
+                &self.column,
+                rowid,
+                self.read_only,
+            ),
+        };
+        Some(match opened {
+            Ok(blob) => {
+                self.blob = Some(blob);
+                Ok(self.blob.as_mut().unwrap())
+            }
+            Err(err) => Err(err),
+        })
+    }
+}
+
+// sort and sweep a group's (beg,end) intervals into their disjoint union, merging any pair
+// whose beg falls within (or immediately after) the running interval
+fn merge_intervals(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    intervals.sort_by_key(|&(beg, _)| beg);
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(intervals.len());
+    for (beg, end) in intervals {
+        match merged.last_mut() {
+            Some((_, current_end)) if beg <= *current_end => {
+                *current_end = (*current_end).max(end);
+            }
+            _ => merged.push((beg, end)),
+        }
+    }
+    merged
+}
+
+// shared step logic for the gri_merge* aggregates: buffer the group's (beg,end) pairs,
+// ignoring any row with a NULL argument
+fn gri_merge_step(ctx: &rusqlite::functions::Context<'_>, acc: &mut Vec<(i64, i64)>) -> Result<()> {
+    let beg: Option<i64> = ctx.get(0)?;
+    let end: Option<i64> = ctx.get(1)?;
+    if let (Some(beg), Some(end)) = (beg, end) {
+        acc.push((beg, end));
+    }
+    Ok(())
+}
+
+struct GriMerge;
+
+impl rusqlite::functions::Aggregate<Vec<(i64, i64)>, Option<String>> for GriMerge {
+    fn init(&self, _ctx: &mut rusqlite::functions::Context<'_>) -> Result<Vec<(i64, i64)>> {
+        Ok(Vec::new())
+    }
+
+    fn step(
+        &self,
+        ctx: &mut rusqlite::functions::Context<'_>,
+        acc: &mut Vec<(i64, i64)>,
+    ) -> Result<()> {
+        gri_merge_step(ctx, acc)
+    }
+
+    fn finalize(
+        &self,
+        _ctx: &mut rusqlite::functions::Context<'_>,
+        acc: Option<Vec<(i64, i64)>>,
+    ) -> Result<Option<String>> {
+        let merged = merge_intervals(acc.unwrap_or_default());
+        if merged.is_empty() {
+            return Ok(None);
+        }
+        let segments: Vec<Value> = merged
+            .into_iter()
+            .map(|(beg, end)| serde_json::json!([beg, end]))
+            .collect();
+        Ok(Some(serde_json::to_string(&segments).unwrap()))
+    }
+}
+
+struct GriMergeLength;
+
+impl rusqlite::functions::Aggregate<Vec<(i64, i64)>, i64> for GriMergeLength {
+    fn init(&self, _ctx: &mut rusqlite::functions::Context<'_>) -> Result<Vec<(i64, i64)>> {
+        Ok(Vec::new())
+    }
+
+    fn step(
+        &self,
+        ctx: &mut rusqlite::functions::Context<'_>,
+        acc: &mut Vec<(i64, i64)>,
+    ) -> Result<()> {
+        gri_merge_step(ctx, acc)
+    }
+
+    fn finalize(
+        &self,
+        _ctx: &mut rusqlite::functions::Context<'_>,
+        acc: Option<Vec<(i64, i64)>>,
+    ) -> Result<i64> {
+        Ok(merge_intervals(acc.unwrap_or_default())
+            .into_iter()
+            .map(|(beg, end)| end - beg)
+            .sum())
+    }
 }
 
 impl ConnectionMethods for Connection {
@@ -255,7 +495,11 @@ impl ConnectionMethods for Connection {
         query1str(
             self,
             "SELECT genomicsqlite_attach_sql(?,?,?)",
-            params![path.as_ref().to_str(), schema_name, config.dump()],
+            params![
+                path.as_ref().to_str(),
+                schema_name,
+                serde_json::to_string(config).unwrap()
+            ],
         )
     }
 
@@ -267,7 +511,7 @@ impl ConnectionMethods for Connection {
         query1str(
             self,
             "SELECT genomicsqlite_vacuum_into_sql(?,?)",
-            params![path.as_ref().to_str(), config.dump()],
+            params![path.as_ref().to_str(), serde_json::to_string(config).unwrap()],
         )
     }
 
@@ -323,6 +567,12 @@ impl ConnectionMethods for Connection {
         refseq: &RefSeq,
         schema: &str,
     ) -> Result<String> {
+        let meta_json_str = serde_json::to_string(&refseq.meta_json).map_err(|e| {
+            rusqlite::Error::ModuleError(format!(
+                "genomicsqlite::put_reference_sequence_sql: invalid meta_json: {}",
+                e
+            ))
+        })?;
         query1str(
             self,
             "SELECT put_genomic_reference_sequence_sql(?,?,?,?,?,?)",
@@ -330,7 +580,7 @@ impl ConnectionMethods for Connection {
                 refseq.length,
                 refseq.assembly,
                 refseq.refget_id,
-                refseq.meta_json.dump(),
+                meta_json_str,
                 refseq.rid,
                 schema
             ],
@@ -366,13 +616,12 @@ impl ConnectionMethods for Connection {
                 length: row.get(2)?,
                 assembly: row.get(3)?,
                 refget_id: row.get(4)?,
-                meta_json: match json::parse(&meta_json_str.as_str()) {
-                    Ok(json::JsonValue::Object(obj)) => Ok(obj),
-                    _ => Err(rusqlite::Error::ModuleError(
-                        "genomicsqlite::get_reference_sequences_by_rid: invalid meta_json"
-                            .to_string(),
-                    )),
-                }?,
+                meta_json: serde_json::from_str(&meta_json_str).map_err(|e| {
+                    rusqlite::Error::ModuleError(format!(
+                        "genomicsqlite::get_reference_sequences_by_rid: invalid meta_json: {}",
+                        e
+                    ))
+                })?,
             })
         })?;
         let mut ans = HashMap::new();
@@ -401,6 +650,219 @@ impl ConnectionMethods for Connection {
         }
         Ok(ans)
     }
+
+    fn backup_genomicsqlite<P: AsRef<Path>>(
+        &self,
+        dest_path: P,
+        dest_config: &Object,
+        pages_per_step: i32,
+        pause_between_pages: Duration,
+        progress: Option<&mut dyn FnMut(rusqlite::backup::Progress)>,
+    ) -> Result<()> {
+        let mut dest = open(
+            dest_path,
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            dest_config,
+        )?;
+        {
+            let backup = Backup::new(self, &mut dest)?;
+            let mut progress = progress;
+            loop {
+                let step_result = backup.step(pages_per_step)?;
+                if let Some(ref mut callback) = progress {
+                    callback(backup.progress());
+                }
+                match step_result {
+                    rusqlite::backup::StepResult::Done => break,
+                    rusqlite::backup::StepResult::More => {
+                        std::thread::sleep(pause_between_pages);
+                    }
+                    rusqlite::backup::StepResult::Busy | rusqlite::backup::StepResult::Locked => {
+                        std::thread::sleep(pause_between_pages);
+                    }
+                }
+            }
+        }
+        // Checkpoint the destination now that the backup has completed. wal_checkpoint only has
+        // an effect if the destination is in WAL mode, which the compressing VFS doesn't support.
+        dest.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+        Ok(())
+    }
+
+    fn open_genomic_blob(
+        &self,
+        schema: &str,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        read_only: bool,
+    ) -> Result<Blob> {
+        self.blob_open(database_name(schema), table, column, rowid, read_only)
+    }
+
+    fn open_genomic_blob_range(
+        &self,
+        schema: &str,
+        table: &str,
+        column: &str,
+        rid: i64,
+        beg: i64,
+        end: i64,
+        read_only: bool,
+    ) -> Result<GenomicBlobRange> {
+        let qualified_table = if schema.is_empty() {
+            table.to_string()
+        } else {
+            format!("{}.{}", schema, table)
+        };
+        let mut stmt = self.prepare("SELECT * FROM genomic_range_rowids(?,?,?,?)")?;
+        let rowids: Vec<i64> = stmt
+            .query_map(params![qualified_table, rid, beg, end], |row| row.get(0))?
+            .collect::<Result<Vec<i64>>>()?;
+        Ok(GenomicBlobRange {
+            conn: self,
+            schema: schema.to_string(),
+            table: table.to_string(),
+            column: column.to_string(),
+            read_only,
+            rowids: rowids.into_iter(),
+            blob: None,
+        })
+    }
+
+    fn create_genomic_interval_functions(&self) -> Result<()> {
+        let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+
+        self.create_scalar_function("gri_overlap", 4, flags, |ctx| -> Result<Option<i64>> {
+            let (beg_a, end_a, beg_b, end_b): (
+                Option<i64>,
+                Option<i64>,
+                Option<i64>,
+                Option<i64>,
+            ) = (ctx.get(0)?, ctx.get(1)?, ctx.get(2)?, ctx.get(3)?);
+            let (beg_a, end_a, beg_b, end_b) = match (beg_a, end_a, beg_b, end_b) {
+                (Some(beg_a), Some(end_a), Some(beg_b), Some(end_b)) => {
+                    (beg_a, end_a, beg_b, end_b)
+                }
+                _ => return Ok(None),
+            };
+            Ok(Some((end_a.min(end_b) - beg_a.max(beg_b)).max(0)))
+        })?;
+
+        self.create_scalar_function("gri_contains", 4, flags, |ctx| -> Result<Option<bool>> {
+            let (beg_a, end_a, beg_b, end_b): (
+                Option<i64>,
+                Option<i64>,
+                Option<i64>,
+                Option<i64>,
+            ) = (ctx.get(0)?, ctx.get(1)?, ctx.get(2)?, ctx.get(3)?);
+            let (beg_a, end_a, beg_b, end_b) = match (beg_a, end_a, beg_b, end_b) {
+                (Some(beg_a), Some(end_a), Some(beg_b), Some(end_b)) => {
+                    (beg_a, end_a, beg_b, end_b)
+                }
+                _ => return Ok(None),
+            };
+            Ok(Some(beg_a <= beg_b && end_b <= end_a))
+        })?;
+
+        self.create_aggregate_function("gri_merge", 2, flags, GriMerge)?;
+        self.create_aggregate_function("gri_merge_length", 2, flags, GriMergeLength)?;
+
+        Ok(())
+    }
+}
+
+/// How to resolve a row-level conflict encountered while applying a changeset with
+/// [apply_genomic_changeset]. Mirrors SQLite's `SQLITE_CHANGESET_{OMIT,REPLACE,ABORT}` actions.
+#[cfg(feature = "session")]
+#[derive(Clone, Copy, Debug)]
+pub enum ChangesetConflictResolution {
+    Omit,
+    Replace,
+    Abort,
+}
+
+#[cfg(feature = "session")]
+impl ChangesetConflictResolution {
+    fn to_action(self) -> ConflictAction {
+        match self {
+            ChangesetConflictResolution::Omit => ConflictAction::SQLITE_CHANGESET_OMIT,
+            ChangesetConflictResolution::Replace => ConflictAction::SQLITE_CHANGESET_REPLACE,
+            ChangesetConflictResolution::Abort => ConflictAction::SQLITE_CHANGESET_ABORT,
+        }
+    }
+}
+
+/// Records INSERT/UPDATE/DELETE activity against one or more attached feature tables during a
+/// transaction (via the SQLite session extension) and emits it as a compact binary changeset,
+/// for incrementally replicating a GenomicSQLite database instead of re-`VACUUM`ing the whole
+/// file. Call [GenomicSession::attach] for each table to record, run the transaction's writes as
+/// usual, then call [GenomicSession::changeset]; apply the result elsewhere with
+/// [apply_genomic_changeset].
+#[cfg(feature = "session")]
+pub struct GenomicSession<'conn> {
+    inner: Session<'conn>,
+}
+
+#[cfg(feature = "session")]
+impl<'conn> GenomicSession<'conn> {
+    /// Begin tracking changes on `conn`.
+    pub fn new(conn: &'conn Connection) -> Result<Self> {
+        Ok(GenomicSession {
+            inner: Session::new(conn)?,
+        })
+    }
+
+    /// Start recording INSERT/UPDATE/DELETE activity on `table_name`.
+    pub fn attach(&mut self, table_name: &str) -> Result<()> {
+        self.inner.attach(Some(table_name))
+    }
+
+    /// Emit everything recorded so far as an opaque changeset.
+    pub fn changeset(&mut self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.inner.changeset_strm(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// True if nothing has been recorded yet.
+    pub fn is_empty(&mut self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+/// Apply a changeset produced by [GenomicSession::changeset] to `conn`, resolving row-level
+/// conflicts with `on_conflict`. If `gri_table` is given as
+/// `(table_name, chromosome, begin_pos, end_pos)`, the table's Genomic Range Index is
+/// regenerated afterward (via [ConnectionMethods::create_genomic_range_index_sql]) so it stays
+/// consistent with the replicated rows.
+#[cfg(feature = "session")]
+pub fn apply_genomic_changeset(
+    conn: &Connection,
+    changeset: &[u8],
+    on_conflict: ChangesetConflictResolution,
+    gri_table: Option<(&str, &str, &str, &str)>,
+) -> Result<()> {
+    conn.apply_strm(
+        &mut std::io::Cursor::new(changeset),
+        None::<fn(&str) -> bool>,
+        |_conflict_type: ConflictType, _item| on_conflict.to_action(),
+    )?;
+    if let Some((table_name, chromosome, begin_pos, end_pos)) = gri_table {
+        let gri_sql =
+            conn.create_genomic_range_index_sql(table_name, chromosome, begin_pos, end_pos)?;
+        conn.execute_batch(&gri_sql)?;
+    }
+    Ok(())
+}
+
+/// Invert a changeset produced by [GenomicSession::changeset], yielding one that reverses its
+/// effect when applied with [apply_genomic_changeset] -- i.e. an undo changeset.
+#[cfg(feature = "session")]
+pub fn invert_genomic_changeset(changeset: &[u8]) -> Result<Vec<u8>> {
+    let mut inverted = Vec::new();
+    rusqlite::session::invert_strm(&mut std::io::Cursor::new(changeset), &mut inverted)?;
+    Ok(inverted)
 }
 
 #[cfg(test)]
@@ -414,8 +876,8 @@ mod tests {
             "/tmp/genomicsqlite_rust_smoke_test.{}.db",
             uuid::Uuid::new_v4()
         );
-        let mut config = json::object::Object::new();
-        config.insert("threads", json::JsonValue::from(3));
+        let mut config = super::Object::new();
+        config.insert("threads".to_string(), serde_json::json!(3));
         let mut conn = super::open(
             dbfn,
             OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
@@ -475,7 +937,7 @@ mod tests {
 
     #[test]
     fn web_test() {
-        let config = json::object::Object::new();
+        let config = super::Object::new();
         let conn = super::open(
             "https://github.com/mlin/sqlite_zstd_vfs/releases/download/web-test-db-v1/TxDb.Hsapiens.UCSC.hg38.knownGene.vacuum.genomicsqlite",
             OpenFlags::SQLITE_OPEN_READ_ONLY,
@@ -488,4 +950,263 @@ mod tests {
             .unwrap();
         assert_eq!(ans, 12);
     }
+
+    #[test]
+    fn backup_test() {
+        let src_fn = format!(
+            "/tmp/genomicsqlite_rust_backup_src.{}.db",
+            uuid::Uuid::new_v4()
+        );
+        let dest_fn = format!(
+            "/tmp/genomicsqlite_rust_backup_dest.{}.db",
+            uuid::Uuid::new_v4()
+        );
+        let src = super::open(
+            &src_fn,
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            &super::Object::new(),
+        )
+        .unwrap();
+        src.execute_batch(
+            "CREATE TABLE feature(rid INTEGER, beg INTEGER, end INTEGER);
+            INSERT INTO feature VALUES(3, 12, 34)",
+        )
+        .unwrap();
+
+        src.backup_genomicsqlite(
+            &dest_fn,
+            &super::Object::new(),
+            -1,
+            std::time::Duration::from_millis(0),
+            None,
+        )
+        .unwrap();
+
+        let dest = super::open(
+            &dest_fn,
+            OpenFlags::SQLITE_OPEN_READ_ONLY,
+            &super::Object::new(),
+        )
+        .unwrap();
+        let ans: i64 = dest
+            .query_row("SELECT COUNT(*) FROM feature", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(ans, 1);
+    }
+
+    #[test]
+    fn blob_range_test() {
+        let dbfn = format!(
+            "/tmp/genomicsqlite_rust_blob_test.{}.db",
+            uuid::Uuid::new_v4()
+        );
+        let mut conn = super::open(
+            dbfn,
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            &super::Object::new(),
+        )
+        .unwrap();
+        {
+            let txn = conn.transaction().unwrap();
+            txn.execute_batch(
+                "CREATE TABLE feature(rid INTEGER, beg INTEGER, end INTEGER, payload BLOB);
+                INSERT INTO feature VALUES(3, 12, 34, x'010203');
+                INSERT INTO feature VALUES(3, 34, 56, x'0405')",
+            )
+            .unwrap();
+            let gri_sql = txn
+                .create_genomic_range_index_sql("feature", "rid", "beg", "end")
+                .unwrap();
+            txn.execute_batch(&gri_sql).unwrap();
+            txn.commit().unwrap();
+        }
+
+        let mut range = conn
+            .open_genomic_blob_range("", "feature", "payload", 3, 12, 56, true)
+            .unwrap();
+        let mut payloads = Vec::new();
+        while let Some(blob) = range.next() {
+            let blob = blob.unwrap();
+            let mut buf = Vec::new();
+            std::io::Read::read_to_end(blob, &mut buf).unwrap();
+            payloads.push(buf);
+        }
+        payloads.sort();
+        let mut expected = vec![vec![1u8, 2, 3], vec![4u8, 5]];
+        expected.sort();
+        assert_eq!(payloads, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "session")]
+    fn changeset_test() {
+        let dbfn1 = format!(
+            "/tmp/genomicsqlite_rust_changeset_src.{}.db",
+            uuid::Uuid::new_v4()
+        );
+        let conn1 = super::open(
+            dbfn1,
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            &super::Object::new(),
+        )
+        .unwrap();
+        conn1
+            .execute_batch("CREATE TABLE feature(rid INTEGER, beg INTEGER, end INTEGER)")
+            .unwrap();
+        let gri_sql = conn1
+            .create_genomic_range_index_sql("feature", "rid", "beg", "end")
+            .unwrap();
+        conn1.execute_batch(&gri_sql).unwrap();
+
+        let mut session = super::GenomicSession::new(&conn1).unwrap();
+        session.attach("feature").unwrap();
+        assert!(session.is_empty());
+        conn1
+            .execute("INSERT INTO feature VALUES(3, 12, 34)", NO_PARAMS)
+            .unwrap();
+        assert!(!session.is_empty());
+        let changeset = session.changeset().unwrap();
+        assert!(!changeset.is_empty());
+
+        let dbfn2 = format!(
+            "/tmp/genomicsqlite_rust_changeset_dest.{}.db",
+            uuid::Uuid::new_v4()
+        );
+        let conn2 = super::open(
+            dbfn2,
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            &super::Object::new(),
+        )
+        .unwrap();
+        conn2
+            .execute_batch("CREATE TABLE feature(rid INTEGER, beg INTEGER, end INTEGER)")
+            .unwrap();
+        let gri_sql2 = conn2
+            .create_genomic_range_index_sql("feature", "rid", "beg", "end")
+            .unwrap();
+        conn2.execute_batch(&gri_sql2).unwrap();
+
+        super::apply_genomic_changeset(
+            &conn2,
+            &changeset,
+            super::ChangesetConflictResolution::Replace,
+            Some(("feature", "rid", "beg", "end")),
+        )
+        .unwrap();
+
+        let ans: i64 = conn2
+            .query_row(
+                "SELECT COUNT(*) FROM genomic_range_rowids('feature',3,34,34)",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(ans, 1);
+    }
+
+    #[test]
+    fn gri_functions_test() {
+        let conn = super::open(
+            ":memory:",
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            &super::Object::new(),
+        )
+        .unwrap();
+        conn.create_genomic_interval_functions().unwrap();
+
+        let overlap: i64 = conn
+            .query_row("SELECT gri_overlap(10,20,15,25)", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(overlap, 5);
+
+        let contains: bool = conn
+            .query_row("SELECT gri_contains(10,30,15,20)", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(contains);
+
+        conn.execute_batch(
+            "CREATE TABLE iv(beg INTEGER, end INTEGER);
+            INSERT INTO iv VALUES(0,10),(5,15),(20,30)",
+        )
+        .unwrap();
+        let merged: String = conn
+            .query_row("SELECT gri_merge(beg,end) FROM iv", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(merged, "[[0,15],[20,30]]");
+        let merged_length: i64 = conn
+            .query_row("SELECT gri_merge_length(beg,end) FROM iv", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(merged_length, 25);
+    }
+
+    #[test]
+    fn busy_timeout_test() {
+        let dbfn = format!(
+            "/tmp/genomicsqlite_rust_busy_test.{}.db",
+            uuid::Uuid::new_v4()
+        );
+        let conn = super::open_with_busy(
+            dbfn,
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            &super::Object::new(),
+            Some(250),
+            None,
+        )
+        .unwrap();
+        let ans: i64 = conn
+            .query_row("PRAGMA busy_timeout", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(ans, 250);
+    }
+
+    #[test]
+    fn tuning_options_and_meta_json_test() {
+        let dbfn = format!(
+            "/tmp/genomicsqlite_rust_tuning_test.{}.db",
+            uuid::Uuid::new_v4()
+        );
+        let tuning = super::TuningOptions {
+            threads: Some(2),
+            ..Default::default()
+        };
+        let conn = super::open_with(
+            dbfn,
+            OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_READ_WRITE,
+            &tuning,
+        )
+        .unwrap();
+        let ans: i64 = conn
+            .query_row("PRAGMA threads", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(ans, 2);
+
+        conn.execute_batch(
+            &conn
+                .put_reference_assembly_sql("GRCh38_no_alt_analysis_set")
+                .unwrap(),
+        )
+        .unwrap();
+        let meta_json = serde_json::json!({"source": "tuning_options_and_meta_json_test"});
+        let refseq = super::RefSeq {
+            rid: -1,
+            name: "chr3_meta_json_test".to_string(),
+            length: 198295559,
+            assembly: Some("GRCh38_no_alt_analysis_set".to_string()),
+            refget_id: None,
+            meta_json: meta_json.clone(),
+        };
+        conn.execute_batch(&conn.put_reference_sequence_sql(&refseq).unwrap())
+            .unwrap();
+        let refseqs = conn.get_reference_sequences_by_name().unwrap();
+        let chr3 = refseqs.get("chr3_meta_json_test").unwrap();
+        assert_eq!(chr3.meta_json, meta_json);
+    }
 }